@@ -0,0 +1,132 @@
+use std::io::Cursor;
+use std::io::prelude::*;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::hive::{Hive, HiveError};
+
+/// Offset of the first hive bin, relative to the start of the file. All
+/// cell offsets stored in `nk`/`vk`/list records are relative to this
+/// point, not to the start of the file.
+pub(crate) const FIRST_HBIN_OFFSET: u64 = 0x1000;
+
+/// Resolves cell offsets into raw cell bytes, enforcing the invariants
+/// that keep a malformed hive from driving the parser off the end of the
+/// file: every cell must fit inside the hive's byte source and must be
+/// allocated (a negative size field; a positive one marks a free cell).
+///
+/// The bytes returned are the cell body only -- the 4-byte size prefix
+/// is not included. Callers that expect a record (`nk`, `vk`, `lf`, ...)
+/// are responsible for checking the 2-byte signature at the front of the
+/// body themselves, since a raw data cell has no signature at all.
+pub(crate) struct CellReader<'a> {
+    hive: &'a Hive,
+}
+
+impl<'a> CellReader<'a> {
+    pub(crate) fn new(hive: &'a Hive) -> CellReader<'a> {
+        CellReader { hive: hive }
+    }
+
+    pub(crate) fn read(&self, rel_offset: u32) -> Result<&'a [u8], HiveError> {
+        let data = self.hive.source.as_slice();
+        let abs_offset = (FIRST_HBIN_OFFSET + rel_offset as u64) as usize;
+
+        if abs_offset + 4 > data.len() {
+            return Err(HiveError::corrupted(abs_offset as u64, "cell offset past end of file"));
+        }
+
+        let mut size_rdr = Cursor::new(&data[abs_offset..abs_offset + 4]);
+        let size = try!(size_rdr.read_i32::<LittleEndian>()
+                        .map_err(HiveError::CannotReadData));
+
+        // A positive size marks a free cell -- only allocated (negative)
+        // cells hold live records.
+        if size >= 0 {
+            return Err(HiveError::corrupted(abs_offset as u64, "cell is unallocated (free)"));
+        }
+
+        // `size` is `i32::MIN` for a maximally-sized crafted cell, which
+        // has no positive negation -- checked_neg catches that instead of
+        // panicking (debug) or silently wrapping (release).
+        let cell_len = match size.checked_neg() {
+            Some(negated) => negated as usize,
+            None => return Err(HiveError::corrupted(abs_offset as u64, "cell length field cannot be negated")),
+        };
+
+        let cell_end = match abs_offset.checked_add(cell_len) {
+            Some(end) => end,
+            None => return Err(HiveError::corrupted(abs_offset as u64, "cell length overflows file offset")),
+        };
+
+        if cell_len < 4 || cell_end > data.len() {
+            return Err(HiveError::corrupted(abs_offset as u64, "cell length invalid or extends past end of file"));
+        }
+
+        Ok(&data[abs_offset + 4..cell_end])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hive::Hive;
+
+    /// Builds a minimal valid hive base block (signature, matching
+    /// sequence numbers, correct XOR checksum), zero-padded up to
+    /// `total_len` bytes, for exercising `CellReader` against crafted
+    /// cell data placed after `FIRST_HBIN_OFFSET`.
+    fn test_hive_bytes(total_len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; total_len];
+        bytes[0..4].copy_from_slice(b"regf");
+        bytes[4..8].copy_from_slice(&1u32.to_le_bytes());
+        bytes[8..12].copy_from_slice(&1u32.to_le_bytes());
+
+        let mut xor: u32 = 0;
+        for chunk in bytes[0..508].chunks(4) {
+            xor ^= u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        bytes[508..512].copy_from_slice(&xor.to_le_bytes());
+
+        bytes
+    }
+
+    #[test]
+    fn rejects_i32_min_cell_size_without_overflowing() {
+        let mut bytes = test_hive_bytes(FIRST_HBIN_OFFSET as usize + 4);
+        let start = FIRST_HBIN_OFFSET as usize;
+        bytes[start..start + 4].copy_from_slice(&i32::MIN.to_le_bytes());
+
+        let hive = Hive::from_vec(bytes).expect("well-formed test hive");
+        let result = CellReader::new(&hive).read(0);
+
+        assert!(matches!(result, Err(HiveError::Corrupted { .. })));
+    }
+
+    #[test]
+    fn rejects_cell_length_that_extends_past_end_of_file() {
+        // Claims an 8-byte cell, but only 2 bytes follow before the
+        // file ends.
+        let mut bytes = test_hive_bytes(FIRST_HBIN_OFFSET as usize + 6);
+        let start = FIRST_HBIN_OFFSET as usize;
+        bytes[start..start + 4].copy_from_slice(&(-8i32).to_le_bytes());
+
+        let hive = Hive::from_vec(bytes).expect("well-formed test hive");
+        let result = CellReader::new(&hive).read(0);
+
+        assert!(matches!(result, Err(HiveError::Corrupted { .. })));
+    }
+
+    #[test]
+    fn returns_the_allocated_cell_body() {
+        let mut bytes = test_hive_bytes(FIRST_HBIN_OFFSET as usize + 8);
+        let start = FIRST_HBIN_OFFSET as usize;
+        bytes[start..start + 4].copy_from_slice(&(-8i32).to_le_bytes());
+        bytes[start + 4..start + 8].copy_from_slice(b"abcd");
+
+        let hive = Hive::from_vec(bytes).expect("well-formed test hive");
+        let body = CellReader::new(&hive).read(0).unwrap();
+
+        assert_eq!(body, b"abcd");
+    }
+}
@@ -0,0 +1,174 @@
+use std::io::Cursor;
+use std::io::prelude::*;
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+
+use crate::hive::HiveError;
+
+/// `REG_SZ`: a NUL-terminated string.
+const REG_SZ: u32 = 1;
+/// `REG_EXPAND_SZ`: a string containing unexpanded `%ENV_VAR%` references.
+const REG_EXPAND_SZ: u32 = 2;
+/// `REG_BINARY`: an opaque blob of bytes.
+const REG_BINARY: u32 = 3;
+/// `REG_DWORD`: a little-endian 32-bit integer.
+const REG_DWORD: u32 = 4;
+/// `REG_DWORD_BIG_ENDIAN`: a big-endian 32-bit integer.
+const REG_DWORD_BIG_ENDIAN: u32 = 5;
+/// `REG_MULTI_SZ`: a sequence of NUL-terminated strings, ending in an
+/// empty one.
+const REG_MULTI_SZ: u32 = 7;
+/// `REG_QWORD`: a little-endian 64-bit integer.
+const REG_QWORD: u32 = 11;
+
+/// A registry value's data, decoded according to its `REG_*` type.
+///
+/// Mirrors the shape of the `registry` crate's `Data` type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Data {
+    String(String),
+    ExpandString(String),
+    MultiString(Vec<String>),
+    Binary(Vec<u8>),
+    U32(u32),
+    U64(u64),
+}
+
+impl Data {
+    /// Decodes `raw` according to `data_type`, one of the `REG_*`
+    /// constants stored in a `vk` record. `offset` is the absolute file
+    /// offset the data was read from, threaded through purely so any
+    /// `HiveError::Corrupted` raised here points at the cell that
+    /// actually failed rather than a generic 0.
+    pub(crate) fn decode(data_type: u32, raw: &[u8], offset: u64) -> Result<Data, HiveError> {
+        match data_type {
+            REG_SZ => Ok(Data::String(try!(utf16_string(raw, offset)))),
+            REG_EXPAND_SZ => Ok(Data::ExpandString(try!(utf16_string(raw, offset)))),
+            REG_MULTI_SZ => Ok(Data::MultiString(try!(utf16_multi_string(raw, offset)))),
+            REG_BINARY => Ok(Data::Binary(raw.to_vec())),
+
+            REG_DWORD => {
+                let mut rdr = Cursor::new(raw);
+                Ok(Data::U32(try!(rdr.read_u32::<LittleEndian>()
+                                  .map_err(HiveError::CannotReadData))))
+            },
+
+            REG_DWORD_BIG_ENDIAN => {
+                let mut rdr = Cursor::new(raw);
+                Ok(Data::U32(try!(rdr.read_u32::<BigEndian>()
+                                  .map_err(HiveError::CannotReadData))))
+            },
+
+            REG_QWORD => {
+                let mut rdr = Cursor::new(raw);
+                Ok(Data::U64(try!(rdr.read_u64::<LittleEndian>()
+                                  .map_err(HiveError::CannotReadData))))
+            },
+
+            _ => Err(HiveError::corrupted(offset, format!("unrecognized REG_* data type {}", data_type))),
+        }
+    }
+}
+
+/// Decodes a single NUL-terminated (or unterminated) little-endian UTF-16
+/// string. `offset` is the absolute file offset `raw` was read from, for
+/// error reporting.
+pub(crate) fn utf16_string(raw: &[u8], offset: u64) -> Result<String, HiveError> {
+    let units = raw_to_utf16_units(raw);
+    let units = match units.iter().position(|&u| u == 0) {
+        Some(nul) => &units[..nul],
+        None => &units[..],
+    };
+
+    String::from_utf16(units).map_err(|_| HiveError::corrupted(offset, "invalid UTF-16 string data"))
+}
+
+/// Decodes a `REG_MULTI_SZ` blob: consecutive NUL-terminated UTF-16
+/// strings, the whole sequence ending in a double NUL (i.e. an empty
+/// string). `offset` is the absolute file offset `raw` was read from, for
+/// error reporting.
+fn utf16_multi_string(raw: &[u8], offset: u64) -> Result<Vec<String>, HiveError> {
+    let units = raw_to_utf16_units(raw);
+
+    let mut strings = Vec::new();
+    let mut start = 0;
+
+    for (i, &unit) in units.iter().enumerate() {
+        if unit != 0 {
+            continue;
+        }
+
+        if i == start {
+            // an empty string marks the end of the sequence
+            break;
+        }
+
+        let s = try!(String::from_utf16(&units[start..i])
+                     .map_err(|_| HiveError::corrupted(offset, "invalid UTF-16 string data")));
+        strings.push(s);
+        start = i + 1;
+    }
+
+    Ok(strings)
+}
+
+fn raw_to_utf16_units(raw: &[u8]) -> Vec<u16> {
+    raw.chunks(2)
+        .map(|chunk| {
+            if chunk.len() == 2 {
+                u16::from(chunk[0]) | (u16::from(chunk[1]) << 8)
+            } else {
+                u16::from(chunk[0])
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_reg_sz_as_utf16le_string() {
+        let raw = [b'h', 0, b'i', 0, 0, 0];
+        let data = Data::decode(REG_SZ, &raw, 0).unwrap();
+        assert_eq!(data, Data::String("hi".to_string()));
+    }
+
+    #[test]
+    fn decodes_reg_dword_little_endian() {
+        let raw = 7u32.to_le_bytes();
+        let data = Data::decode(REG_DWORD, &raw, 0).unwrap();
+        assert_eq!(data, Data::U32(7));
+    }
+
+    #[test]
+    fn decodes_reg_dword_big_endian() {
+        let raw = 7u32.to_be_bytes();
+        let data = Data::decode(REG_DWORD_BIG_ENDIAN, &raw, 0).unwrap();
+        assert_eq!(data, Data::U32(7));
+    }
+
+    #[test]
+    fn decodes_reg_multi_sz_into_separate_strings() {
+        let mut raw = Vec::new();
+        for s in &["a", "bb"] {
+            for unit in s.encode_utf16() {
+                raw.extend_from_slice(&unit.to_le_bytes());
+            }
+            raw.extend_from_slice(&[0, 0]); // terminates this string
+        }
+        raw.extend_from_slice(&[0, 0]); // terminates the sequence
+
+        let data = Data::decode(REG_MULTI_SZ, &raw, 0).unwrap();
+        assert_eq!(data, Data::MultiString(vec!["a".to_string(), "bb".to_string()]));
+    }
+
+    #[test]
+    fn unrecognized_type_reports_the_offset_it_was_read_at() {
+        match Data::decode(0xffff, &[], 0x1234) {
+            Err(HiveError::Corrupted { offset, .. }) => assert_eq!(offset, 0x1234),
+            _ => panic!("expected a Corrupted error"),
+        }
+    }
+}
@@ -2,14 +2,27 @@ use std::io;
 use std::fmt;
 use std::error;
 use std::result;
+use std::env;
 use std::fs::File;
 use std::path::Path;
 use std::io::Cursor;
-use std::io::SeekFrom;
 use std::io::prelude::*;
+use std::backtrace::Backtrace;
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
+#[cfg(feature = "mmap")]
+use memmap::Mmap;
+
+use crate::key::KeyNode;
+use crate::log;
+use crate::search;
+use crate::source::Source;
+
+/// Offset, within the base block, of the root cell's offset (relative to
+/// the first hive bin).
+const ROOT_CELL_OFFSET_FIELD: usize = 0x24;
+
 /// The HiveError type. Every sort of error is represented in this enum.
 #[derive(Debug)]
 pub enum HiveError {
@@ -17,8 +30,41 @@ pub enum HiveError {
     CannotOpenHive(io::Error),
     /// Data could not be read. Carries with it the underlying io::Error.
     CannotReadData(io::Error),
-    /// The hive is invalid.
-    InvalidHive
+    /// The hive (or a cell within it) is corrupt.
+    Corrupted {
+        /// File offset at which validation failed.
+        offset: u64,
+        /// A short, human-readable reason, e.g. "bad cell signature,
+        /// expected 'nk'".
+        explanation: String,
+        /// Captured when `RUST_BACKTRACE` is set, to aid forensic
+        /// debugging of where in the parser the check failed.
+        backtrace: Option<Backtrace>,
+    },
+    /// `open_key` was given a path with no matching key.
+    KeyNotFound,
+    /// The hive's primary and secondary sequence numbers disagree (it
+    /// wasn't cleanly unmounted) and no transaction log could recover it.
+    /// See `Hive::new_with_logs`.
+    Dirty,
+}
+
+impl HiveError {
+    /// Builds a `HiveError::Corrupted` for the check failing at file
+    /// offset `offset`, capturing a backtrace if `RUST_BACKTRACE` is set.
+    pub(crate) fn corrupted<S: Into<String>>(offset: u64, explanation: S) -> HiveError {
+        let backtrace = if env::var_os("RUST_BACKTRACE").is_some() {
+            Some(Backtrace::capture())
+        } else {
+            None
+        };
+
+        HiveError::Corrupted {
+            offset: offset,
+            explanation: explanation.into(),
+            backtrace: backtrace,
+        }
+    }
 }
 
 impl fmt::Display for HiveError {
@@ -28,8 +74,12 @@ impl fmt::Display for HiveError {
                 write!(f, "Unable to open hive path: {}", err),
             HiveError::CannotReadData(ref err) =>
                 write!(f, "Could not read data: {}", err),
-            HiveError::InvalidHive =>
-                write!(f, "Invalid or corrupt hive"),
+            HiveError::Corrupted { offset, ref explanation, .. } =>
+                write!(f, "Corrupt hive at offset 0x{:x}: {}", offset, explanation),
+            HiveError::KeyNotFound =>
+                write!(f, "No key found at the given path"),
+            HiveError::Dirty =>
+                write!(f, "Hive is dirty and could not be recovered from its logs"),
         }
     }
 }
@@ -39,7 +89,9 @@ impl error::Error for HiveError {
         match *self {
             HiveError::CannotOpenHive(ref err) => err.description(),
             HiveError::CannotReadData(ref err) => err.description(),
-            HiveError::InvalidHive => "Invalid or corrupt hive",
+            HiveError::Corrupted { ref explanation, .. } => explanation,
+            HiveError::KeyNotFound => "No key found at the given path",
+            HiveError::Dirty => "Hive is dirty and could not be recovered from its logs",
         }
     }
 
@@ -47,13 +99,16 @@ impl error::Error for HiveError {
         match *self {
             HiveError::CannotOpenHive(ref err) => Some(err),
             HiveError::CannotReadData(ref err) => Some(err),
-            HiveError::InvalidHive => None,
+            HiveError::Corrupted { .. } => None,
+            HiveError::KeyNotFound => None,
+            HiveError::Dirty => None,
         }
     }
 }
 
 pub struct Hive {
-    f: File,
+    pub(crate) source: Source,
+    root_cell_offset: u32,
 }
 
 impl Hive {
@@ -78,63 +133,193 @@ impl Hive {
     /// data could not be read, or if the file is not a valid registry hive.
     ///
     pub fn new<P: AsRef<Path>>(path: P) -> result::Result<Hive, HiveError> {
-        // open file
         let mut reg_file = try!(File::open(path.as_ref())
                       .map_err(HiveError::CannotOpenHive));
 
-        // check file signature
-        let mut file_sig = [0; 4];
-        let actual_sig: [u8; 4] = [0x72, 0x65, 0x67, 0x66]; //"regf" ascii chars
-
-        try!(reg_file.read_exact(&mut file_sig)
+        let mut bytes = Vec::new();
+        try!(reg_file.read_to_end(&mut bytes)
              .map_err(HiveError::CannotReadData));
 
-        if !file_sig.eq(&actual_sig) {
-            return Err(HiveError::InvalidHive);
-        }
+        Hive::from_vec(bytes)
+    }
+
+    /// Builds a `Hive` over an in-memory copy of `bytes`. Useful when the
+    /// hive came from somewhere other than the filesystem, e.g. a
+    /// forensic image or a network stream.
+    pub fn from_bytes(bytes: &[u8]) -> result::Result<Hive, HiveError> {
+        Hive::from_vec(bytes.to_vec())
+    }
+
+    /// Like `from_bytes`, but takes ownership of an existing buffer
+    /// instead of copying one.
+    pub fn from_vec(bytes: Vec<u8>) -> result::Result<Hive, HiveError> {
+        let root_cell_offset = try!(validate_header(&bytes));
+
+        Ok(Hive {
+            source: Source::Bytes(bytes),
+            root_cell_offset: root_cell_offset,
+        })
+    }
+
+    /// Memory-maps the file at `path` instead of reading it into a
+    /// buffer, so traversal pages in only the parts of the hive it
+    /// actually visits. Requires the `mmap` feature.
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap<P: AsRef<Path>>(path: P) -> result::Result<Hive, HiveError> {
+        let reg_file = try!(File::open(path.as_ref())
+                      .map_err(HiveError::CannotOpenHive));
+
+        let mmap = try!(unsafe { Mmap::map(&reg_file) }
+                        .map_err(HiveError::CannotReadData));
+
+        let root_cell_offset = try!(validate_header(&mmap));
+
+        Ok(Hive {
+            source: Source::Mmap(mmap),
+            root_cell_offset: root_cell_offset,
+        })
+    }
 
-        // check sequence numbers
-        let mut primary = [0; 4];
-        let mut secondary = [0; 4];
-        try!(reg_file.read_exact(&mut primary)
+    /// Opens a hive that may be "dirty" (its primary and secondary
+    /// sequence numbers disagree, meaning it wasn't cleanly unmounted)
+    /// by replaying its `.LOG1`/`.LOG2` transaction logs before parsing.
+    ///
+    /// `log_paths` is tried in order; the first log whose starting
+    /// sequence number matches the hive's secondary sequence number is
+    /// replayed and the rest are ignored. If the hive isn't dirty, this
+    /// is equivalent to `Hive::new`.
+    ///
+    /// # Errors
+    /// Returns `HiveError::Dirty` if the hive is dirty and none of
+    /// `log_paths` can recover it.
+    pub fn new_with_logs<P: AsRef<Path>>(hive_path: P,
+                                         log_paths: &[P])
+                                         -> result::Result<Hive, HiveError> {
+        let mut reg_file = try!(File::open(hive_path.as_ref())
+                      .map_err(HiveError::CannotOpenHive));
+
+        let mut bytes = Vec::new();
+        try!(reg_file.read_to_end(&mut bytes)
              .map_err(HiveError::CannotReadData));
-        try!(reg_file.read_exact(&mut secondary).
-             map_err(HiveError::CannotReadData));
 
-        if !primary.eq(&secondary) {
-            return Err(HiveError::InvalidHive);
+        if bytes.len() < 12 {
+            return Err(HiveError::corrupted(0, "file too short to contain sequence numbers"));
         }
 
-        // do the XOR checksum
-        let mut header_raw = [0; 508];
-        let mut header_check = [0; 4];
-        let mut xor: u32 = 0;
+        let primary = try!(Cursor::new(&bytes[4..8]).read_u32::<LittleEndian>()
+                           .map_err(HiveError::CannotReadData));
+        let secondary = try!(Cursor::new(&bytes[8..12]).read_u32::<LittleEndian>()
+                            .map_err(HiveError::CannotReadData));
 
-        try!(reg_file.seek(SeekFrom::Start(0))
-             .map_err(HiveError::CannotReadData));
-        try!(reg_file.read_exact(&mut header_raw)
-             .map_err(HiveError::CannotReadData));
-        try!(reg_file.read_exact(&mut header_check)
-             .map_err(HiveError::CannotReadData));
+        if primary == secondary {
+            return Hive::from_vec(bytes);
+        }
 
-        let mut header_rdr = Cursor::new(header_raw.to_vec());
-        let mut check_rdr = Cursor::new(header_check);
+        for log_path in log_paths {
+            // replay onto a copy, so a log that fails part-way through
+            // leaves nothing behind for the next one to build on
+            let mut recovered = bytes.clone();
+            let recovered_seq = match log::apply_log(&mut recovered, log_path, secondary) {
+                Ok(Some(seq)) => seq,
+                Ok(None) | Err(_) => continue,
+            };
 
-        let csum = check_rdr.read_u32::<LittleEndian>().unwrap();
+            let seq_bytes = recovered_seq.to_le_bytes();
+            recovered[4..8].copy_from_slice(&seq_bytes);
+            recovered[8..12].copy_from_slice(&seq_bytes);
+            rewrite_checksum(&mut recovered);
 
-        for _ in 0..127 { // 508 / 4, the number of u32s in the vec
-            xor ^= try!(header_rdr.read_u32::<LittleEndian>()
-                        .map_err(HiveError::CannotReadData));
+            return Hive::from_vec(recovered);
         }
 
-        if xor != csum {
-            return Err(HiveError::InvalidHive);
+        Err(HiveError::Dirty)
+    }
+
+    /// Returns the root `KeyNode` of the hive, the entry point for
+    /// traversing the rest of the key/value tree.
+    pub fn root_key(&self) -> result::Result<KeyNode<'_>, HiveError> {
+        KeyNode::read(self, self.root_cell_offset)
+    }
+
+    /// Walks a backslash-delimited path, such as
+    /// `r"Software\Microsoft\Windows"`, down from the root key and
+    /// returns the matching `KeyNode`. Path components are matched
+    /// case-insensitively, the way the Windows registry does.
+    ///
+    /// # Errors
+    /// Returns `HiveError::KeyNotFound` if no subkey matches at some
+    /// component of the path, distinct from the errors that indicate the
+    /// hive itself is corrupt.
+    pub fn open_key(&self, path: &str) -> result::Result<KeyNode<'_>, HiveError> {
+        let mut current = try!(self.root_key());
+
+        for component in path.split('\\').filter(|c| !c.is_empty()) {
+            current = try!(current.open_subkey(component));
         }
 
-        // reset the seek ptr to 0 before instantiating
-        try!(reg_file.seek(SeekFrom::Start(0))
-             .map_err(HiveError::CannotReadData));
+        Ok(current)
+    }
+
+    /// Walks the whole key tree and returns every key whose full
+    /// backslash path matches the glob `pattern` (e.g.
+    /// `Software\*\Uninstall\*`), case-insensitively.
+    pub fn find<'a>(&'a self, pattern: &str) -> result::Result<impl Iterator<Item = KeyNode<'a>>, HiveError> {
+        search::find(self, pattern).map(IntoIterator::into_iter)
+    }
+}
 
-        Ok(Hive {f: reg_file})
+/// Recomputes the XOR checksum over the 508-byte header and writes it
+/// back into the checksum field, after log replay has changed the
+/// sequence numbers the checksum covers.
+fn rewrite_checksum(data: &mut [u8]) {
+    let mut xor: u32 = 0;
+    let mut header_rdr = Cursor::new(&data[0..508]);
+    for _ in 0..127 {
+        xor ^= header_rdr.read_u32::<LittleEndian>().unwrap();
     }
+
+    data[508..512].copy_from_slice(&xor.to_le_bytes());
+}
+
+/// Runs the base-block checks (signature, sequence numbers, XOR
+/// checksum) against an in-memory view of the hive, and returns the root
+/// cell offset once they pass.
+fn validate_header(data: &[u8]) -> result::Result<u32, HiveError> {
+    if data.len() < 512 {
+        return Err(HiveError::corrupted(0, "file too short to contain a hive base block"));
+    }
+
+    let actual_sig: [u8; 4] = [0x72, 0x65, 0x67, 0x66]; //"regf" ascii chars
+    if data[0..4] != actual_sig {
+        return Err(HiveError::corrupted(0, "bad file signature, expected 'regf'"));
+    }
+
+    let primary = &data[4..8];
+    let secondary = &data[8..12];
+    if primary != secondary {
+        return Err(HiveError::corrupted(4, "primary and secondary sequence numbers do not match"));
+    }
+
+    let header_raw = &data[0..508];
+    let mut check_rdr = Cursor::new(&data[508..512]);
+    let csum = check_rdr.read_u32::<LittleEndian>().unwrap();
+
+    let mut xor: u32 = 0;
+    let mut header_rdr = Cursor::new(header_raw);
+    for _ in 0..127 { // 508 / 4, the number of u32s in the header
+        xor ^= try!(header_rdr.read_u32::<LittleEndian>()
+                    .map_err(HiveError::CannotReadData));
+    }
+
+    if xor != csum {
+        return Err(HiveError::corrupted(508, "header XOR checksum mismatch"));
+    }
+
+    // the root cell offset is a little-endian u32 living inside the
+    // header region we already have in hand
+    let mut root_rdr = Cursor::new(&header_raw[ROOT_CELL_OFFSET_FIELD..]);
+    let root_cell_offset = try!(root_rdr.read_u32::<LittleEndian>()
+                                .map_err(HiveError::CannotReadData));
+
+    Ok(root_cell_offset)
 }
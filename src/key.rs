@@ -0,0 +1,246 @@
+use std::io::Cursor;
+use std::io::SeekFrom;
+use std::io::prelude::*;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::cell::{CellReader, FIRST_HBIN_OFFSET};
+use crate::data::utf16_string;
+use crate::hive::{Hive, HiveError};
+use crate::value::ValueNode;
+
+const NK_SIGNATURE: &'static [u8] = b"nk";
+
+/// Marks a subkey-list or value-list offset field as "not present".
+const NO_OFFSET: u32 = 0xffff_ffff;
+
+/// Bit in the `nk` flags field marking the name as stored ASCII/compressed
+/// rather than UTF-16LE.
+const NK_COMPRESSED_NAME_FLAG: u16 = 0x0020;
+
+/// A parsed `nk` (key node) record.
+///
+/// Holds the key's name plus enough bookkeeping from the record to
+/// resolve its subkeys and values lazily, on demand, rather than eagerly
+/// walking the whole tree up front.
+pub struct KeyNode<'a> {
+    hive: &'a Hive,
+    pub name: String,
+    /// Absolute file offset of this key's `nk` cell, kept around so
+    /// callers outside this module (e.g. the glob search's cycle guard)
+    /// can report corruption errors that point at a real location.
+    pub(crate) cell_offset: u64,
+    subkey_count: u32,
+    subkey_list_offset: u32,
+    value_count: u32,
+    value_list_offset: u32,
+}
+
+impl<'a> KeyNode<'a> {
+    /// Parses the `nk` record at `rel_offset` (relative to the first hive
+    /// bin, as all cell offsets are).
+    pub(crate) fn read(hive: &'a Hive, rel_offset: u32) -> Result<KeyNode<'a>, HiveError> {
+        let raw = try!(CellReader::new(hive).read(rel_offset));
+        let offset = FIRST_HBIN_OFFSET + rel_offset as u64;
+
+        if raw.len() < 2 || &raw[0..2] != NK_SIGNATURE {
+            return Err(HiveError::corrupted(offset, "bad cell signature, expected 'nk'"));
+        }
+
+        let mut body = Cursor::new(&raw[2..]);
+
+        let flags = try!(body.read_u16::<LittleEndian>().map_err(HiveError::CannotReadData));
+
+        // timestamp(8) + spare(4) + parent offset(4) = 16 bytes
+        try!(body.seek(SeekFrom::Current(16)).map_err(HiveError::CannotReadData));
+
+        let subkey_count = try!(body.read_u32::<LittleEndian>()
+                                .map_err(HiveError::CannotReadData));
+        try!(body.seek(SeekFrom::Current(4)).map_err(HiveError::CannotReadData)); // volatile subkey count
+        let subkey_list_offset = try!(body.read_u32::<LittleEndian>()
+                                      .map_err(HiveError::CannotReadData));
+        try!(body.seek(SeekFrom::Current(4)).map_err(HiveError::CannotReadData)); // volatile subkey list offset
+
+        let value_count = try!(body.read_u32::<LittleEndian>()
+                               .map_err(HiveError::CannotReadData));
+        let value_list_offset = try!(body.read_u32::<LittleEndian>()
+                                     .map_err(HiveError::CannotReadData));
+
+        // security key offset(4) + class name offset(4) + four "largest"
+        // fields(16) + work var(4) = 28 bytes
+        try!(body.seek(SeekFrom::Current(28)).map_err(HiveError::CannotReadData));
+
+        let name_len = try!(body.read_u16::<LittleEndian>()
+                            .map_err(HiveError::CannotReadData));
+        try!(body.seek(SeekFrom::Current(2)).map_err(HiveError::CannotReadData)); // class name length
+
+        let mut name_raw = vec![0; name_len as usize];
+        try!(body.read_exact(&mut name_raw).map_err(HiveError::CannotReadData));
+
+        // The compressed-name flag means the name is stored one byte per
+        // character (ASCII); otherwise it's UTF-16LE, same as value data.
+        let name = if flags & NK_COMPRESSED_NAME_FLAG != 0 {
+            String::from_utf8_lossy(&name_raw).into_owned()
+        } else {
+            try!(utf16_string(&name_raw, offset))
+        };
+
+        Ok(KeyNode {
+            hive: hive,
+            name: name,
+            cell_offset: offset,
+            subkey_count: subkey_count,
+            subkey_list_offset: subkey_list_offset,
+            value_count: value_count,
+            value_list_offset: value_list_offset,
+        })
+    }
+
+    /// This key's direct subkeys, in the order the subkey list stores
+    /// them.
+    pub fn subkeys(&self) -> Result<Vec<KeyNode<'a>>, HiveError> {
+        if self.subkey_count == 0 || self.subkey_list_offset == NO_OFFSET {
+            return Ok(Vec::new());
+        }
+
+        let offsets = try!(collect_subkey_offsets(self.hive, self.subkey_list_offset, 0));
+
+        offsets.into_iter()
+            .map(|offset| KeyNode::read(self.hive, offset))
+            .collect()
+    }
+
+    /// Finds the direct subkey named `name`, matching case-insensitively.
+    pub(crate) fn open_subkey(&self, name: &str) -> Result<KeyNode<'a>, HiveError> {
+        try!(self.subkeys())
+            .into_iter()
+            .find(|subkey| subkey.name.eq_ignore_ascii_case(name))
+            .ok_or(HiveError::KeyNotFound)
+    }
+
+    /// This key's values, in the order the value list stores them.
+    pub fn values(&self) -> Result<Vec<ValueNode<'a>>, HiveError> {
+        if self.value_count == 0 || self.value_list_offset == NO_OFFSET {
+            return Ok(Vec::new());
+        }
+
+        let raw = try!(CellReader::new(self.hive).read(self.value_list_offset));
+        let mut body = Cursor::new(raw);
+
+        let mut values = Vec::with_capacity(self.value_count as usize);
+        for _ in 0..self.value_count {
+            let offset = try!(body.read_u32::<LittleEndian>()
+                              .map_err(HiveError::CannotReadData));
+            values.push(try!(ValueNode::read(self.hive, offset)));
+        }
+
+        Ok(values)
+    }
+}
+
+/// How many `ri` index-of-indexes levels `collect_subkey_offsets` will
+/// follow before giving up. Chosen generously above any nesting depth a
+/// real hive's subkey list would need, so a self-referential or
+/// absurdly deep crafted `ri` chain is rejected with a `HiveError`
+/// instead of overflowing the stack.
+const MAX_SUBKEY_LIST_DEPTH: u32 = 32;
+
+/// Walks an `lf`/`lh`/`li` subkey list -- following `ri` index-of-indexes
+/// blocks as needed -- and returns the flat list of `nk` offsets it
+/// names.
+fn collect_subkey_offsets(hive: &Hive, rel_offset: u32, depth: u32) -> Result<Vec<u32>, HiveError> {
+    let offset = FIRST_HBIN_OFFSET + rel_offset as u64;
+
+    if depth > MAX_SUBKEY_LIST_DEPTH {
+        return Err(HiveError::corrupted(offset, "subkey list nested too deep (possible 'ri' cycle)"));
+    }
+
+    let raw = try!(CellReader::new(hive).read(rel_offset));
+
+    if raw.len() < 4 {
+        return Err(HiveError::corrupted(offset, "subkey list cell too short to hold a header"));
+    }
+
+    let signature = &raw[0..2];
+    let mut body = Cursor::new(&raw[2..]);
+    let count = try!(body.read_u16::<LittleEndian>().map_err(HiveError::CannotReadData));
+
+    match signature {
+        b"ri" => {
+            let mut offsets = Vec::new();
+            for _ in 0..count {
+                let list_offset = try!(body.read_u32::<LittleEndian>()
+                                       .map_err(HiveError::CannotReadData));
+                offsets.extend(try!(collect_subkey_offsets(hive, list_offset, depth + 1)));
+            }
+            Ok(offsets)
+        },
+
+        b"lf" | b"lh" => {
+            let mut offsets = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let key_offset = try!(body.read_u32::<LittleEndian>()
+                                      .map_err(HiveError::CannotReadData));
+                try!(body.seek(SeekFrom::Current(4)).map_err(HiveError::CannotReadData)); // hash
+                offsets.push(key_offset);
+            }
+            Ok(offsets)
+        },
+
+        b"li" => {
+            let mut offsets = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                offsets.push(try!(body.read_u32::<LittleEndian>()
+                                  .map_err(HiveError::CannotReadData)));
+            }
+            Ok(offsets)
+        },
+
+        _ => Err(HiveError::corrupted(offset, "bad subkey list signature, expected 'lf', 'lh', 'li', or 'ri'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a minimal valid hive base block (signature, matching
+    /// sequence numbers, correct XOR checksum), zero-padded up to
+    /// `total_len` bytes, for exercising key/subkey-list parsing
+    /// against crafted cell data placed after `FIRST_HBIN_OFFSET`.
+    fn test_hive_bytes(total_len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; total_len];
+        bytes[0..4].copy_from_slice(b"regf");
+        bytes[4..8].copy_from_slice(&1u32.to_le_bytes());
+        bytes[8..12].copy_from_slice(&1u32.to_le_bytes());
+
+        let mut xor: u32 = 0;
+        for chunk in bytes[0..508].chunks(4) {
+            xor ^= u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        bytes[508..512].copy_from_slice(&xor.to_le_bytes());
+
+        bytes
+    }
+
+    #[test]
+    fn collect_subkey_offsets_rejects_a_self_referential_ri_list() {
+        // An 'ri' list whose single entry points back at its own cell,
+        // which would otherwise recurse forever.
+        let mut body = Vec::new();
+        body.extend_from_slice(b"ri");
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes());
+
+        let cell_len = 4 + body.len();
+        let mut bytes = test_hive_bytes(FIRST_HBIN_OFFSET as usize + cell_len);
+        let start = FIRST_HBIN_OFFSET as usize;
+        bytes[start..start + 4].copy_from_slice(&(-(cell_len as i32)).to_le_bytes());
+        bytes[start + 4..start + cell_len].copy_from_slice(&body);
+
+        let hive = Hive::from_vec(bytes).expect("well-formed test hive");
+        let result = collect_subkey_offsets(&hive, 0, 0);
+
+        assert!(matches!(result, Err(HiveError::Corrupted { .. })));
+    }
+}
@@ -0,0 +1,155 @@
+use std::fs::File;
+use std::io::Cursor;
+use std::io::prelude::*;
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::hive::HiveError;
+
+/// Transaction logs carry their own `regf`-style base block before the
+/// dirty-page blocks start.
+const LOG_HEADER_LEN: usize = 512;
+
+const DIRT_SIGNATURE: &'static [u8] = b"DIRT";
+const HVLE_SIGNATURE: &'static [u8] = b"HvLE";
+
+/// If `log_path` is a transaction log that picks up where the hive's
+/// secondary sequence number left off, replays its dirty pages onto
+/// `hive_bytes` in place and returns the sequence number the hive ends
+/// up at. Returns `Ok(None)` if the log's starting sequence number
+/// doesn't match, so the caller can try the next one.
+pub(crate) fn apply_log<P: AsRef<Path>>(hive_bytes: &mut [u8],
+                                        log_path: P,
+                                        secondary_seq: u32)
+                                        -> Result<Option<u32>, HiveError> {
+    let mut file = try!(File::open(log_path).map_err(HiveError::CannotOpenHive));
+    let mut log_bytes = Vec::new();
+    try!(file.read_to_end(&mut log_bytes).map_err(HiveError::CannotReadData));
+
+    if log_bytes.len() < LOG_HEADER_LEN || &log_bytes[0..4] != b"regf" {
+        return Ok(None);
+    }
+
+    let start_seq = try!(read_u32(&log_bytes, 4));
+    if start_seq != secondary_seq {
+        return Ok(None);
+    }
+
+    let mut pos = LOG_HEADER_LEN;
+    let mut last_seq = start_seq;
+
+    while pos + 16 <= log_bytes.len() {
+        let signature = &log_bytes[pos..pos + 4];
+        if signature != DIRT_SIGNATURE && signature != HVLE_SIGNATURE {
+            break;
+        }
+
+        let block_size = try!(read_u32(&log_bytes, pos + 4)) as usize;
+        let block_seq = try!(read_u32(&log_bytes, pos + 8));
+        let page_count = try!(read_u32(&log_bytes, pos + 12));
+
+        if block_size < 16 || pos + block_size > log_bytes.len() {
+            return Err(HiveError::corrupted(pos as u64, "dirty page block size invalid or extends past end of log"));
+        }
+
+        let mut descriptor_pos = pos + 16;
+        let descriptor_table_len = match (page_count as usize).checked_mul(8) {
+            Some(len) => len,
+            None => return Err(HiveError::corrupted(pos as u64, "dirty page count overflows descriptor table size")),
+        };
+        let mut data_pos = descriptor_pos + descriptor_table_len;
+
+        if data_pos > pos + block_size {
+            return Err(HiveError::corrupted(pos as u64, "dirty page descriptor table extends past end of block"));
+        }
+
+        for _ in 0..page_count {
+            let offset = try!(read_u32(&log_bytes, descriptor_pos)) as usize;
+            let size = try!(read_u32(&log_bytes, descriptor_pos + 4)) as usize;
+            descriptor_pos += 8;
+
+            if offset + size > hive_bytes.len() {
+                return Err(HiveError::corrupted(offset as u64, "dirty page target offset past end of hive"));
+            }
+
+            if data_pos + size > log_bytes.len() {
+                return Err(HiveError::corrupted(data_pos as u64, "dirty page data extends past end of log"));
+            }
+
+            hive_bytes[offset..offset + size]
+                .copy_from_slice(&log_bytes[data_pos..data_pos + size]);
+            data_pos += size;
+        }
+
+        last_seq = block_seq;
+        pos += block_size;
+    }
+
+    Ok(Some(last_seq))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, HiveError> {
+    let end = match offset.checked_add(4) {
+        Some(end) if end <= data.len() => end,
+        _ => return Err(HiveError::corrupted(offset as u64, "field extends past end of log")),
+    };
+
+    Cursor::new(&data[offset..end])
+        .read_u32::<LittleEndian>()
+        .map_err(HiveError::CannotReadData)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    fn write_temp_log(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("registry-rs-test-{}-{}.log", name, std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(bytes).unwrap();
+        path
+    }
+
+    fn minimal_log_header(start_seq: u32) -> Vec<u8> {
+        let mut bytes = vec![0u8; LOG_HEADER_LEN];
+        bytes[0..4].copy_from_slice(b"regf");
+        bytes[4..8].copy_from_slice(&start_seq.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn apply_log_rejects_page_count_that_overflows_the_block_instead_of_panicking() {
+        let mut log_bytes = minimal_log_header(1);
+
+        // A DIRT block with block_size=16 (room for just the block
+        // header) but a page_count of 5, which would run the
+        // descriptor table 40 bytes past the end of the block.
+        log_bytes.extend_from_slice(b"DIRT");
+        log_bytes.extend_from_slice(&16u32.to_le_bytes());
+        log_bytes.extend_from_slice(&1u32.to_le_bytes());
+        log_bytes.extend_from_slice(&5u32.to_le_bytes());
+
+        let path = write_temp_log("oversized-page-count", &log_bytes);
+        let mut hive_bytes = vec![0u8; 4096];
+        let result = apply_log(&mut hive_bytes, &path, 1);
+        fs::remove_file(&path).ok();
+
+        assert!(matches!(result, Err(HiveError::Corrupted { .. })));
+    }
+
+    #[test]
+    fn read_u32_rejects_offset_past_end_of_buffer_instead_of_panicking() {
+        let data = [1u8, 2, 3];
+        assert!(matches!(read_u32(&data, 0), Err(HiveError::Corrupted { .. })));
+    }
+
+    #[test]
+    fn read_u32_reads_a_little_endian_value_in_range() {
+        let data = 0x01020304u32.to_le_bytes();
+        assert_eq!(read_u32(&data, 0).unwrap(), 0x01020304);
+    }
+}
@@ -1,4 +1,11 @@
 mod hive;
+mod cell;
+mod key;
+mod value;
+mod data;
+mod source;
+mod log;
+mod search;
 
 use std::env;
 use std::process;
@@ -0,0 +1,210 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::hive::{Hive, HiveError};
+use crate::key::KeyNode;
+
+/// Translates a backslash-delimited registry glob (e.g.
+/// `Software\*\Uninstall\*`) into an anchored regex, following the
+/// approach of Mercurial's `filepatterns` module: `**` spans path
+/// segments, a bare `*` stays within a single segment, `?` matches one
+/// non-separator character, and everything else is escaped literally.
+fn glob_to_regex(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut regex = String::from("^");
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                regex.push_str(r"(?:.*\\)?");
+                i += 2;
+                if chars.get(i) == Some(&'\\') {
+                    i += 1;
+                }
+            },
+
+            '*' => {
+                regex.push_str(r"[^\\]*");
+                i += 1;
+            },
+
+            '?' => {
+                regex.push_str(r"[^\\]");
+                i += 1;
+            },
+
+            c => {
+                regex.push_str(&::regex::escape(&c.to_string()));
+                i += 1;
+            },
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+/// Compiles `pattern` into a case-insensitive matcher for full key
+/// paths. The translation in `glob_to_regex` only ever emits
+/// known-valid regex fragments, so compilation can't fail.
+fn compile_glob(pattern: &str) -> Regex {
+    RegexBuilder::new(&glob_to_regex(pattern))
+        .case_insensitive(true)
+        .build()
+        .expect("glob pattern translates to a valid regex")
+}
+
+/// How many key-tree levels `walk` will descend before giving up.
+/// Chosen generously above any nesting depth a real hive's key tree
+/// would need, so a subkey list that cycles back to an ancestor is
+/// rejected with a `HiveError` instead of recursing forever.
+const MAX_KEY_DEPTH: u32 = 256;
+
+/// Walks the key tree depth-first, collecting every key whose full
+/// backslash path (relative to the root) matches `pattern`. The root key
+/// itself has no path of its own and is never returned.
+pub(crate) fn find<'a>(hive: &'a Hive, pattern: &str) -> Result<Vec<KeyNode<'a>>, HiveError> {
+    let regex = compile_glob(pattern);
+    let root = try!(hive.root_key());
+
+    // paths start below the root, the same way `Hive::open_key` reads them
+    let mut matches = Vec::new();
+    for subkey in try!(root.subkeys()) {
+        try!(walk(subkey, String::new(), &regex, &mut matches, 1));
+    }
+
+    Ok(matches)
+}
+
+fn walk<'a>(key: KeyNode<'a>,
+            parent_path: String,
+            regex: &Regex,
+            matches: &mut Vec<KeyNode<'a>>,
+            depth: u32)
+            -> Result<(), HiveError> {
+    if depth > MAX_KEY_DEPTH {
+        return Err(HiveError::corrupted(key.cell_offset, "key tree nested too deep (possible subkey cycle)"));
+    }
+
+    let path = if parent_path.is_empty() {
+        key.name.clone()
+    } else {
+        format!("{}\\{}", parent_path, key.name)
+    };
+
+    let subkeys = try!(key.subkeys());
+    let is_match = regex.is_match(&path);
+
+    if is_match {
+        matches.push(key);
+    }
+
+    for subkey in subkeys {
+        try!(walk(subkey, path.clone(), regex, matches, depth + 1));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::FIRST_HBIN_OFFSET;
+
+    #[test]
+    fn glob_double_star_spans_path_segments() {
+        let regex = compile_glob(r"Software\**\Uninstall");
+        assert!(regex.is_match(r"Software\Uninstall"));
+        assert!(regex.is_match(r"Software\Microsoft\Windows\Uninstall"));
+    }
+
+    #[test]
+    fn glob_single_star_stays_within_one_segment() {
+        let regex = compile_glob(r"Software\*");
+        assert!(regex.is_match(r"Software\Microsoft"));
+        assert!(!regex.is_match(r"Software\Microsoft\Windows"));
+    }
+
+    #[test]
+    fn glob_matching_is_case_insensitive() {
+        let regex = compile_glob("Software");
+        assert!(regex.is_match("SOFTWARE"));
+    }
+
+    /// Builds a minimal valid hive base block, zero-padded up to
+    /// `total_len` bytes.
+    fn test_hive_bytes(total_len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; total_len];
+        bytes[0..4].copy_from_slice(b"regf");
+        bytes[4..8].copy_from_slice(&1u32.to_le_bytes());
+        bytes[8..12].copy_from_slice(&1u32.to_le_bytes());
+
+        let mut xor: u32 = 0;
+        for chunk in bytes[0..508].chunks(4) {
+            xor ^= u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        bytes[508..512].copy_from_slice(&xor.to_le_bytes());
+
+        bytes
+    }
+
+    /// An `nk` record with a compressed (ASCII) name, no values, and
+    /// the given subkey bookkeeping.
+    fn nk_cell(name: &str, subkey_count: u32, subkey_list_offset: u32) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(b"nk");
+        body.extend_from_slice(&0x0020u16.to_le_bytes()); // flags: compressed name
+        body.extend_from_slice(&[0u8; 16]); // timestamp + spare + parent offset
+        body.extend_from_slice(&subkey_count.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes()); // volatile subkey count
+        body.extend_from_slice(&subkey_list_offset.to_le_bytes());
+        body.extend_from_slice(&0u32.to_le_bytes()); // volatile subkey list offset
+        body.extend_from_slice(&0u32.to_le_bytes()); // value count
+        body.extend_from_slice(&0xffff_ffffu32.to_le_bytes()); // value list offset (none)
+        body.extend_from_slice(&[0u8; 28]); // security/class/largest/work var
+        body.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes()); // class name length
+        body.extend_from_slice(name.as_bytes());
+        body
+    }
+
+    /// An `lf` subkey list naming the given key offsets.
+    fn lf_cell(entries: &[u32]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(b"lf");
+        body.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for &offset in entries {
+            body.extend_from_slice(&offset.to_le_bytes());
+            body.extend_from_slice(&[0u8; 4]); // hash, unused by collect_subkey_offsets
+        }
+        body
+    }
+
+    fn place_cell(bytes: &mut Vec<u8>, rel_offset: u32, body: &[u8]) {
+        let cell_len = 4 + body.len();
+        let start = FIRST_HBIN_OFFSET as usize + rel_offset as usize;
+        if bytes.len() < start + cell_len {
+            bytes.resize(start + cell_len, 0);
+        }
+        bytes[start..start + 4].copy_from_slice(&(-(cell_len as i32)).to_le_bytes());
+        bytes[start + 4..start + cell_len].copy_from_slice(body);
+    }
+
+    #[test]
+    fn find_rejects_a_subkey_tree_that_cycles_back_to_an_ancestor() {
+        // The root key (offset 0) has one subkey: itself, via the lf
+        // list at offset 100 -- a self-loop that would otherwise make
+        // `walk` recurse forever.
+        let root = nk_cell("root", 1, 100);
+        let lf = lf_cell(&[0]);
+
+        let mut bytes = test_hive_bytes(FIRST_HBIN_OFFSET as usize);
+        place_cell(&mut bytes, 0, &root);
+        place_cell(&mut bytes, 100, &lf);
+
+        let hive = Hive::from_vec(bytes).expect("well-formed test hive");
+        let result = find(&hive, "*");
+
+        assert!(matches!(result, Err(HiveError::Corrupted { .. })));
+    }
+}
@@ -0,0 +1,27 @@
+#[cfg(feature = "mmap")]
+use memmap::Mmap;
+
+/// Where a hive's bytes live. Parsing only ever needs a `&[u8]` view of
+/// the whole file, so everything from here down can stay agnostic to
+/// whether that view is backed by an owned buffer or a memory-mapped
+/// file.
+pub(crate) enum Source {
+    /// An in-memory buffer, as used by `Hive::from_bytes`/`from_vec` and
+    /// by `Hive::new`, which reads the whole file up front.
+    Bytes(Vec<u8>),
+    /// A memory-mapped file, as used by `Hive::from_mmap`. Lets the OS
+    /// page in only the parts of the hive that traversal actually
+    /// touches.
+    #[cfg(feature = "mmap")]
+    Mmap(Mmap),
+}
+
+impl Source {
+    pub(crate) fn as_slice(&self) -> &[u8] {
+        match *self {
+            Source::Bytes(ref bytes) => bytes.as_slice(),
+            #[cfg(feature = "mmap")]
+            Source::Mmap(ref mmap) => &mmap[..],
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::Cursor;
+use std::io::SeekFrom;
+use std::io::prelude::*;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::cell::{CellReader, FIRST_HBIN_OFFSET};
+use crate::data::{utf16_string, Data};
+use crate::hive::{Hive, HiveError};
+
+const VK_SIGNATURE: &'static [u8] = b"vk";
+
+/// Signature of a "big data" record, which chains several cells together
+/// to hold data too large (> 16344 bytes) to fit in a single cell.
+const DB_SIGNATURE: &'static [u8] = b"db";
+
+/// Most data bytes a single `db` segment cell holds.
+const BIG_DATA_SEGMENT_LEN: usize = 16344;
+
+/// Bit in the `vk` flags field marking the name as stored ASCII/compressed
+/// rather than UTF-16LE.
+const VK_COMPRESSED_NAME_FLAG: u16 = 0x0001;
+
+/// Bit in the `vk` data-size field marking the data as stored inline, in
+/// the data-offset field itself, rather than in its own cell.
+const INLINE_DATA_FLAG: u32 = 0x8000_0000;
+
+/// A parsed `vk` (value) record.
+pub struct ValueNode<'a> {
+    hive: &'a Hive,
+    pub name: String,
+    data_type: u32,
+    data_size: u32,
+    data_offset: u32,
+}
+
+impl<'a> ValueNode<'a> {
+    /// Parses the `vk` record at `rel_offset`.
+    pub(crate) fn read(hive: &'a Hive, rel_offset: u32) -> Result<ValueNode<'a>, HiveError> {
+        let raw = try!(CellReader::new(hive).read(rel_offset));
+        let offset = FIRST_HBIN_OFFSET + rel_offset as u64;
+
+        if raw.len() < 2 || &raw[0..2] != VK_SIGNATURE {
+            return Err(HiveError::corrupted(offset, "bad cell signature, expected 'vk'"));
+        }
+
+        let mut body = Cursor::new(&raw[2..]);
+
+        let name_len = try!(body.read_u16::<LittleEndian>()
+                            .map_err(HiveError::CannotReadData));
+        let data_size = try!(body.read_u32::<LittleEndian>()
+                             .map_err(HiveError::CannotReadData));
+        let data_offset = try!(body.read_u32::<LittleEndian>()
+                               .map_err(HiveError::CannotReadData));
+        let data_type = try!(body.read_u32::<LittleEndian>()
+                             .map_err(HiveError::CannotReadData));
+
+        let flags = try!(body.read_u16::<LittleEndian>().map_err(HiveError::CannotReadData));
+        try!(body.seek(SeekFrom::Current(2)).map_err(HiveError::CannotReadData)); // spare
+
+        let name = if name_len == 0 {
+            "(default)".to_string()
+        } else {
+            let mut name_raw = vec![0; name_len as usize];
+            try!(body.read_exact(&mut name_raw).map_err(HiveError::CannotReadData));
+
+            // The compressed-name flag means the name is stored one byte
+            // per character (ASCII); otherwise it's UTF-16LE, same as
+            // value data.
+            if flags & VK_COMPRESSED_NAME_FLAG != 0 {
+                String::from_utf8_lossy(&name_raw).into_owned()
+            } else {
+                try!(utf16_string(&name_raw, offset))
+            }
+        };
+
+        Ok(ValueNode {
+            hive: hive,
+            name: name,
+            data_type: data_type,
+            data_size: data_size,
+            data_offset: data_offset,
+        })
+    }
+
+    /// The raw `REG_*` type code (`REG_SZ` = 1, `REG_DWORD` = 4, ...) this
+    /// value's data is stored as.
+    pub fn data_type_raw(&self) -> u32 {
+        self.data_type
+    }
+
+    /// This value's data, decoded according to its `REG_*` type.
+    pub fn data(&self) -> Result<Data, HiveError> {
+        let offset = FIRST_HBIN_OFFSET + self.data_offset as u64;
+        Data::decode(self.data_type, &try!(self.raw_data()), offset)
+    }
+
+    /// Reads this value's raw, undecoded bytes, transparently resolving
+    /// the inline-data optimization (data-size's high bit set means the
+    /// up-to-4 bytes of data live directly in the data-offset field) and
+    /// `db` "big data" chains.
+    fn raw_data(&self) -> Result<Vec<u8>, HiveError> {
+        let size = (self.data_size & !INLINE_DATA_FLAG) as usize;
+
+        if self.data_size & INLINE_DATA_FLAG != 0 {
+            let mut inline = Vec::with_capacity(4);
+            try!(inline.write_u32::<LittleEndian>(self.data_offset)
+                 .map_err(HiveError::CannotReadData));
+            inline.truncate(size);
+            return Ok(inline);
+        }
+
+        let cell = try!(CellReader::new(self.hive).read(self.data_offset));
+
+        let mut data = if cell.len() >= 2 && &cell[0..2] == DB_SIGNATURE {
+            try!(read_big_data(self.hive, cell))
+        } else {
+            cell.to_vec()
+        };
+
+        if data.len() > size {
+            data.truncate(size);
+        }
+
+        Ok(data)
+    }
+}
+
+/// Reassembles the data chained across the segments a `db` record points
+/// to: a segment-list cell holding a run of offsets, each naming a data
+/// cell of up to 16344 bytes.
+fn read_big_data(hive: &Hive, db_body: &[u8]) -> Result<Vec<u8>, HiveError> {
+    let mut header = Cursor::new(&db_body[2..]);
+
+    let segment_count = try!(header.read_u16::<LittleEndian>()
+                             .map_err(HiveError::CannotReadData));
+    let list_offset = try!(header.read_u32::<LittleEndian>()
+                          .map_err(HiveError::CannotReadData));
+
+    let list_raw = try!(CellReader::new(hive).read(list_offset));
+    let mut list = Cursor::new(list_raw);
+
+    let mut data = Vec::new();
+    for _ in 0..segment_count {
+        let segment_offset = try!(list.read_u32::<LittleEndian>()
+                                  .map_err(HiveError::CannotReadData));
+        let segment = try!(CellReader::new(hive).read(segment_offset));
+        // the cell body can run past 16344 bytes with alignment padding
+        data.extend(&segment[..segment.len().min(BIG_DATA_SEGMENT_LEN)]);
+    }
+
+    Ok(data)
+}